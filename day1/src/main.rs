@@ -1,57 +1,122 @@
-use std::{fs, env, collections::HashMap};
+use std::{fs, env, collections::VecDeque, io::BufRead, io::BufReader};
+
+const DIGITS: [(&str, usize); 10] = [("0", 0), ("1", 1), ("2", 2), ("3", 3), ("4", 4), ("5", 5), ("6", 6), ("7", 7), ("8", 8), ("9", 9)];
+const WORDS: [(&str, usize); 10] = [("zero", 0), ("one", 1), ("two", 2), ("three", 3), ("four", 4), ("five", 5), ("six", 6), ("seven", 7), ("eight", 8), ("nine", 9)];
+
+fn vocabulary(words: bool, zero: bool) -> Vec<(&'static str, usize)> {
+    let mut vocabulary: Vec<(&str, usize)> = DIGITS.to_vec();
+    if words {
+        vocabulary.extend(WORDS);
+    }
+    if !zero {
+        vocabulary.retain(|(_, value)| *value != 0);
+    }
+    vocabulary
+}
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let (flags, args): (Vec<String>, Vec<String>) = env::args().partition(|a| a.starts_with("--"));
     if args.len() == 2 {
         let filename = &args[1];
-        let text = fs::read_to_string(&filename)
-            .expect(&format!("Error reading from {}", filename));
-        let map = HashMap::from([
-            //("zero", 0),
-            ("one", 1),
-            ("two", 2),
-            ("three", 3),
-            ("four", 4),
-            ("five", 5),
-            ("six", 6),
-            ("seven", 7),
-            ("eight", 8),
-            ("nine", 9),
-            //("0", 0),
-            ("1", 1),
-            ("2", 2),
-            ("3", 3),
-            ("4", 4),
-            ("5", 5),
-            ("6", 6),
-            ("7", 7),
-            ("8", 8),
-            ("9", 9),
-        ]);
-        let values = text.lines().map(|s| find_calibration_value(s, &map));
-        let sum: usize = values.sum();
-        println!("Sum: {}", sum)
+        let file = fs::File::open(filename)
+            .unwrap_or_else(|_| panic!("Error reading from {}", filename));
+        let zero = flags.iter().any(|f| f == "--zero");
+        let scanners = [
+            ("Digits", Scanner::new(&vocabulary(false, zero))),
+            ("Digits and words", Scanner::new(&vocabulary(true, zero)))
+        ];
+        let mut sums: Vec<Result<u64, String>> = vec![Ok(0); scanners.len()];
+        let mut reader = BufReader::new(file);
+        let mut line = Vec::new();
+        let mut number = 0;
+        // read bytes rather than lines, so huge documents are neither buffered nor utf8 checked
+        while reader.read_until(b'\n', &mut line).expect("Error reading line") > 0 {
+            number += 1;
+            for ((_, scanner), sum) in scanners.iter().zip(sums.iter_mut()) {
+                if let Ok(total) = sum {
+                    *sum = match scanner.find_calibration_value(&line) {
+                        Some(value) => total.checked_add(value as u64).ok_or("Sum overflowed u64".to_string()),
+                        None => Err(format!("No calibration value on line {}", number))
+                    };
+                }
+            }
+            line.clear();
+        }
+        for ((name, _), sum) in scanners.iter().zip(sums) {
+            match sum {
+                Ok(sum) => println!("{} sum: {}", name, sum),
+                Err(e) => println!("{} sum: {}", name, e)
+            }
+        }
     } else {
-        println!("Please provide 1 argument: Filename");
+        println!("Please provide 1 argument: Filename [--zero]");
     }
 }
 
-fn find_calibration_value(line: &str, map: &HashMap<&str, usize>) -> usize {
-    let mut first_index = None;
-    let mut first_value = None;
-    let mut last_index = None;
-    let mut last_value = None;
-    for (key, value) in map.iter() {
-        for (i, _) in line.match_indices(key) {
-            if first_index.is_none() || first_index.unwrap() > i {
-                first_index = Some(i);
-                first_value = Some(value)
+struct Scanner {
+    // Aho-Corasick automaton: every state has a transition for every byte
+    transitions: Vec<[usize; 256]>,
+    // (length, value) of every token which ends at this state, including via suffixes
+    outputs: Vec<Vec<(usize, usize)>>
+}
+
+impl Scanner {
+    fn new(vocabulary: &[(&str, usize)]) -> Self {
+        // build a trie, where 0 is both the root and "no child yet", since nothing points back to the root
+        let mut transitions = vec![[0; 256]];
+        let mut outputs = vec![Vec::new()];
+        for (token, value) in vocabulary {
+            let mut state = 0;
+            for b in token.bytes() {
+                if transitions[state][b as usize] == 0 {
+                    transitions.push([0; 256]);
+                    outputs.push(Vec::new());
+                    transitions[state][b as usize] = transitions.len() - 1;
+                }
+                state = transitions[state][b as usize];
             }
-            if last_index.is_none() || last_index.unwrap() < i {
-                last_index = Some(i);
-                last_value = Some(value);
+            outputs[state].push((token.len(), *value));
+        }
+        // breadth first, fill in the missing transitions from the longest proper suffix (failure) state
+        let mut failure = vec![0; transitions.len()];
+        let mut pending: VecDeque<usize> = transitions[0].iter().copied().filter(|s| *s != 0).collect();
+        while let Some(state) = pending.pop_front() {
+            let fallback = transitions[failure[state]];
+            for (next, fallback_next) in transitions[state].iter_mut().zip(fallback) {
+                if *next != 0 {
+                    failure[*next] = fallback_next;
+                    let inherited = outputs[fallback_next].clone();
+                    outputs[*next].extend(inherited);
+                    pending.push_back(*next);
+                } else {
+                    *next = fallback_next;
+                }
             }
         }
+        Self {
+            transitions,
+            outputs
+        }
     }
-    first_value.unwrap() * 10 + last_value.unwrap()
-}
\ No newline at end of file
+
+    fn find_calibration_value(&self, line: &[u8]) -> Option<usize> {
+        // tokens can overlap (eg. eightwo), so track the earliest and latest start of any match
+        let mut first: Option<(usize, usize, usize)> = None;
+        let mut last: Option<(usize, usize, usize)> = None;
+        let mut state = 0;
+        for (i, b) in line.iter().enumerate() {
+            state = self.transitions[state][*b as usize];
+            for (length, value) in &self.outputs[state] {
+                let start = i + 1 - length;
+                // at the same start, prefer the longest token
+                if first.is_none_or(|(s, l, _)| start < s || (start == s && *length > l)) {
+                    first = Some((start, *length, *value));
+                }
+                if last.is_none_or(|(s, l, _)| start > s || (start == s && *length > l)) {
+                    last = Some((start, *length, *value));
+                }
+            }
+        }
+        Some(first?.2 * 10 + last?.2)
+    }
+}
@@ -2,8 +2,9 @@ use std::fs;
 use std::env;
 use std::str::FromStr;
 use std::collections::HashMap;
+use std::collections::HashSet;
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Copy, Clone, Debug)]
 enum Pipe {
     NorthSouth,
     EastWest,
@@ -15,7 +16,23 @@ enum Pipe {
 }
 
 struct Map {
-    pipes: HashMap<Point, Pipe>
+    pipes: HashMap<Point, Pipe>,
+    start: Point,
+    rows: usize,
+    columns: usize
+}
+
+struct Validation {
+    main_loop: Result<Vec<Point>, String>,
+    dangling: Vec<(Point, Point)>,
+    disconnected: Vec<Point>,
+    loops: Vec<Vec<Point>>
+}
+
+#[derive(PartialEq, Copy, Clone)]
+enum Render {
+    Plain,
+    Colour
 }
 
 #[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
@@ -47,34 +64,70 @@ impl FromStr for Map {
 
     fn from_str(text: &str) -> Result<Self, Self::Err> {
         let mut pipes = HashMap::new();
-        let mut r = 0;
-        for line in text.lines() {
-            let mut c = 0;
-            for ch in line.chars() {
+        let mut starts = Vec::new();
+        let mut columns = 0;
+        for (r, line) in text.lines().enumerate() {
+            columns = columns.max(line.chars().count());
+            for (c, ch) in line.chars().enumerate() {
                 if ch != '.' {
-                    pipes.insert(Point { r: r as isize, c: c as isize }, Pipe::from(ch));
+                    let point = Point { r: r as isize, c: c as isize };
+                    let pipe = Pipe::from(ch).map_err(|e| format!("{} at {:?}", e, point))?;
+                    if pipe == Pipe::Start {
+                        starts.push(point);
+                    }
+                    pipes.insert(point, pipe);
                 }
-                c += 1;
             }
-            r += 1;
+        }
+        if starts.len() != 1 {
+            return Err(format!("Expected 1 start, found {}", starts.len()));
         }
         Ok(Self {
-            pipes
+            pipes,
+            start: starts[0],
+            rows: text.lines().count(),
+            columns
         })
     }
 }
 
 impl Pipe {
-    fn from(c: char) -> Self {
+    const SHAPES: [Pipe; 6] = [Self::NorthSouth, Self::EastWest, Self::NorthEast, Self::NorthWest, Self::SouthWest, Self::SouthEast];
+
+    fn from(c: char) -> Result<Self, String> {
         match c {
-            '|' => Self::NorthSouth,
-            '-' => Self::EastWest,
-            'L' => Self::NorthEast,
-            'J' => Self::NorthWest,
-            '7' => Self::SouthWest,
-            'F' => Self::SouthEast,
-            'S' => Self::Start,
-            _ => panic!("Unknown char: {}", c)
+            '|' => Ok(Self::NorthSouth),
+            '-' => Ok(Self::EastWest),
+            'L' => Ok(Self::NorthEast),
+            'J' => Ok(Self::NorthWest),
+            '7' => Ok(Self::SouthWest),
+            'F' => Ok(Self::SouthEast),
+            'S' => Ok(Self::Start),
+            _ => Err(format!("Unknown char: {}", c))
+        }
+    }
+
+    fn to_char(self) -> char {
+        match self {
+            Self::NorthSouth => '|',
+            Self::EastWest => '-',
+            Self::NorthEast => 'L',
+            Self::NorthWest => 'J',
+            Self::SouthWest => '7',
+            Self::SouthEast => 'F',
+            Self::Start => 'S'
+        }
+    }
+
+    fn to_box_char(self) -> char {
+        match self {
+            Self::NorthSouth => '│',
+            Self::EastWest => '─',
+            Self::NorthEast => '└',
+            Self::NorthWest => '┘',
+            Self::SouthWest => '┐',
+            Self::SouthEast => '┌',
+            Self::Start => 'S'
         }
     }
 
@@ -92,17 +145,57 @@ impl Pipe {
 }
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let (flags, args): (Vec<String>, Vec<String>) = env::args().partition(|a| a.starts_with("--"));
     if args.len() == 2 {
         let filename = &args[1];
-        let text = fs::read_to_string(&filename)
-            .expect(&format!("Error reading from {}", filename));
+        let text = fs::read_to_string(filename)
+            .unwrap_or_else(|_| panic!("Error reading from {}", filename));
         let map: Map = text.parse().unwrap();
-        let longest = map.loop_from_start();
-        println!("Longest loop: {}", longest);
-        println!("Farthest point: {}", (longest+1) / 2);
+        if flags.iter().any(|f| f == "--validate") {
+            map.validate().print(&map);
+            return;
+        }
+        let map = map.normalized().unwrap();
+        let path = map.loop_from_start().unwrap();
+        let render = if flags.iter().any(|f| f == "--plain") {
+            Some(Render::Plain)
+        } else if flags.iter().any(|f| f == "--colour") {
+            Some(Render::Colour)
+        } else {
+            None
+        };
+        if let Some(render) = render {
+            print!("{}", map.render(&path, render, flags.iter().any(|f| f == "--regions")));
+        } else {
+            println!("Start pipe: {}", map.pipes[&map.start].to_char());
+            println!("Longest loop: {}", path.len());
+            println!("Farthest point: {}", path.len().div_ceil(2));
+            println!("Enclosed tiles: {}", Map::enclosed_area(&path));
+        }
     } else {
-        println!("Please provide 1 argument: Filename");
+        println!("Please provide 1 argument: Filename [--plain|--colour] [--regions] [--validate]");
+    }
+}
+
+impl Validation {
+    fn print(&self, map: &Map) {
+        let describe = |p: &Point| format!("{} at ({}, {})", map.pipes.get(p).map(|pipe| pipe.to_char()).unwrap_or('.'), p.r, p.c);
+        match &self.main_loop {
+            Ok(path) => println!("Main loop: {} pipes", path.len()),
+            Err(e) => println!("Main loop: {}", e)
+        }
+        println!("Dangling ends: {}", self.dangling.len());
+        for (point, end) in &self.dangling {
+            println!("  {} points at {}", describe(point), describe(end));
+        }
+        println!("Disconnected from main loop: {}", self.disconnected.len());
+        for point in &self.disconnected {
+            println!("  {}", describe(point));
+        }
+        println!("Separate closed loops: {}", self.loops.len());
+        for path in &self.loops {
+            println!("  {} pipes starting at {}", path.len(), describe(&path[0]));
+        }
     }
 }
 
@@ -126,46 +219,177 @@ impl Map {
         }
     }
 
-    fn loop_from_start(&self) -> usize {
-        let s = self.start();
-        let mut options = Vec::new();
-        for p in Pipe::Start.points(&s) {
-            //println!("Start: {:?}, Next: {:?}", s, p);
-            let longest = self.longest(&s, &p);
-            //println!("Longest: {:?}", longest);
-            if let Some(option) = longest {
-                options.push(option);
+    fn start_shape(&self) -> Result<Pipe, String> {
+        let mut shapes = Vec::new();
+        for shape in Pipe::SHAPES {
+            // both ends must connect back to the start, and following one end must come back via the other
+            let points = shape.points(&self.start);
+            if let Some(path) = self.path(&self.start, &points[0]) {
+                if path.last() == Some(&points[1]) {
+                    shapes.push(shape);
+                }
             }
         }
-        if options.len() == 0 {
-            panic!("No options found");
+        match shapes.len() {
+            0 => Err(format!("No loop passes through start {:?}", self.start)),
+            1 => Ok(shapes[0]),
+            _ => Err(format!("Multiple loops pass through start {:?}, it could be any of: {}", self.start, shapes.iter().map(|s| s.to_char().to_string()).collect::<Vec<_>>().join(" ")))
+        }
+    }
+
+    fn normalized(mut self) -> Result<Self, String> {
+        let shape = self.start_shape()?;
+        self.pipes.insert(self.start, shape);
+        Ok(self)
+    }
+
+    fn loop_from_start(&self) -> Result<Vec<Point>, String> {
+        let shape = self.start_shape()?;
+        Ok(self.path(&self.start, &shape.points(&self.start)[0]).unwrap())
+    }
+
+    fn enclosed_area(path: &[Point]) -> usize {
+        // shoelace formula gives the area of the polygon through the tile centres
+        let mut twice_area = 0;
+        for i in 0..path.len() {
+            let a = path[i];
+            let b = path[(i + 1) % path.len()];
+            twice_area += a.c * b.r - b.c * a.r;
         }
-        options.into_iter().max().unwrap()
+        let area = twice_area.unsigned_abs() / 2;
+        // Pick's theorem: area = inside + boundary / 2 - 1
+        area + 1 - path.len() / 2
     }
 
-    fn start(&self) -> Point {
+    fn validate(&self) -> Validation {
+        let main_loop = self.loop_from_start();
+        let start_shape = self.start_shape().ok();
+        let mut dangling = Vec::new();
         for (point, pipe) in &self.pipes {
-            if *pipe == Pipe::Start {
-                return point.clone();
+            let pipe = if *pipe == Pipe::Start {
+                match start_shape {
+                    Some(shape) => shape,
+                    None => continue
+                }
+            } else {
+                *pipe
+            };
+            for end in pipe.points(point) {
+                // once its shape is known the start is checked like any other pipe, otherwise it only needs to be pointed at
+                let reciprocated = self.pipes.get(&end).is_some_and(|other| match (*other, start_shape) {
+                    (Pipe::Start, Some(shape)) => shape.points(&end).contains(point),
+                    (Pipe::Start, None) => true,
+                    (other, _) => other.points(&end).contains(point)
+                });
+                if !reciprocated {
+                    dangling.push((*point, end));
+                }
+            }
+        }
+        dangling.sort_by_key(|(p, e)| (p.r, p.c, e.r, e.c));
+        let mut visited: HashSet<Point> = main_loop.iter().flatten().copied().collect();
+        let mut disconnected: Vec<Point> = self.pipes.keys().filter(|p| !visited.contains(p)).copied().collect();
+        disconnected.sort_by_key(|p| (p.r, p.c));
+        let mut loops = Vec::new();
+        for point in &disconnected {
+            if visited.contains(point) || self.pipes[point] == Pipe::Start {
+                continue;
+            }
+            let ends = self.pipes[point].points(point);
+            if let Some(path) = self.path(point, &ends[0]) {
+                if path.last() == Some(&ends[1]) {
+                    visited.extend(path.iter().copied());
+                    loops.push(path);
+                }
+            }
+        }
+        Validation {
+            main_loop,
+            dangling,
+            disconnected,
+            loops
+        }
+    }
+
+    fn inside(&self, path: &[Point]) -> HashSet<Point> {
+        // scanning along each row, every loop tile which connects north crosses the boundary
+        let mut crosses = HashSet::new();
+        for i in 0..path.len() {
+            let north = path[i].north();
+            if path[(i + 1) % path.len()] == north || path[(i + path.len() - 1) % path.len()] == north {
+                crosses.insert(path[i]);
+            }
+        }
+        let on_loop: HashSet<Point> = path.iter().copied().collect();
+        let mut inside = HashSet::new();
+        for r in 0..self.rows {
+            let mut is_inside = false;
+            for c in 0..self.columns {
+                let point = Point { r: r as isize, c: c as isize };
+                if crosses.contains(&point) {
+                    is_inside = !is_inside;
+                } else if is_inside && !on_loop.contains(&point) {
+                    inside.insert(point);
+                }
             }
         }
-        panic!("No start found");
+        inside
     }
 
-    fn longest(&self, start: &Point, next: &Point) -> Option<usize> {
+    fn render(&self, path: &[Point], render: Render, regions: bool) -> String {
+        const RESET: &str = "\x1b[0m";
+        const LOOP: &str = "\x1b[1;33m";
+        const JUNK: &str = "\x1b[2m";
+        const INSIDE: &str = "\x1b[42m";
+        const OUTSIDE: &str = "\x1b[44m";
+        let on_loop: HashSet<Point> = path.iter().copied().collect();
+        let inside = if regions { self.inside(path) } else { HashSet::new() };
+        let mut text = String::new();
+        for r in 0..self.rows {
+            for c in 0..self.columns {
+                let point = Point { r: r as isize, c: c as isize };
+                let pipe = self.pipes.get(&point);
+                if on_loop.contains(&point) {
+                    let ch = pipe.unwrap().to_box_char();
+                    match render {
+                        Render::Plain => text.push(ch),
+                        Render::Colour => text.push_str(&format!("{}{}{}", LOOP, ch, RESET))
+                    }
+                } else {
+                    match (render, regions) {
+                        (Render::Plain, true) => text.push(if inside.contains(&point) { 'I' } else { 'O' }),
+                        (Render::Plain, false) => text.push(pipe.map(|p| p.to_char()).unwrap_or('.')),
+                        (Render::Colour, _) => {
+                            let ch = pipe.map(|p| p.to_box_char()).unwrap_or('·');
+                            let background = match (regions, inside.contains(&point)) {
+                                (false, _) => "",
+                                (true, true) => INSIDE,
+                                (true, false) => OUTSIDE
+                            };
+                            text.push_str(&format!("{}{}{}{}", background, JUNK, ch, RESET));
+                        }
+                    }
+                }
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    fn path(&self, start: &Point, next: &Point) -> Option<Vec<Point>> {
         let mut previous = *start;
         let mut current = *next;
-        let mut steps = 1;
+        let mut path = vec![*start];
         while current != *start {
             if let Some(next) = self.next(&previous, &current) {
                 //println!("Next: {:?}", next);
+                path.push(current);
                 previous = current;
                 current = next;
-                steps += 1;
             } else {
                 return None;
             }
         }
-        Some(steps)
+        Some(path)
     }
-}
\ No newline at end of file
+}
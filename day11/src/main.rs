@@ -15,10 +15,22 @@ struct Point {
     c: usize
 }
 
+#[derive(Copy, Clone)]
+enum Ragged {
+    Pad,
+    Reject
+}
+
 impl FromStr for Raw {
     type Err = String;
 
     fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Self::parse(text, Ragged::Reject)
+    }
+}
+
+impl Raw {
+    fn parse(text: &str, ragged: Ragged) -> Result<Self, String> {
         let mut rows = Vec::new();
         for line in text.lines() {
             let mut row = Vec::new();
@@ -27,6 +39,15 @@ impl FromStr for Raw {
             }
             rows.push(row);
         }
+        let columns = rows.iter().map(|r| r.len()).max().unwrap_or(0);
+        for (r, row) in rows.iter_mut().enumerate() {
+            if row.len() != columns {
+                match ragged {
+                    Ragged::Pad => row.resize(columns, false),
+                    Ragged::Reject => return Err(format!("Row {} has {} columns, expected {}", r + 1, row.len(), columns))
+                }
+            }
+        }
         Ok(Self(rows))
     }
 }
@@ -35,14 +56,11 @@ impl Image {
     fn from(raw: Raw) -> Self {
         let mut galaxies = Vec::new();
         let mut row_empty = Vec::new();
-        let mut col_empty = Vec::new();
-        for c in 0..raw.0[0].len() {
-            col_empty.push(true);
-        }
-        for r in 0..raw.0.len() {
+        let mut col_empty = vec![true; raw.0.first().map_or(0, |row| row.len())];
+        for (r, row) in raw.0.iter().enumerate() {
             let mut empty = true;
-            for c in 0..raw.0[r].len() {
-                if raw.0[r][c] {
+            for (c, galaxy) in row.iter().enumerate() {
+                if *galaxy {
                     empty = false;
                     galaxies.push(Point { r, c });
                     col_empty[c] = false;
@@ -57,72 +75,170 @@ impl Image {
         }
     }
 
-    fn distance(&self, a: &Point, b: &Point) -> usize {
-        let mut d = 0;
-        if b.r > a.r {
-            for r in a.r..b.r {
-                if self.row_empty[r] {
-                    d += 1000000;
-                } else {
-                    d += 1;
-                }
-            }
-        } else {
-            for r in b.r..a.r {
-                if self.row_empty[r] {
-                    d += 1000000;
-                } else {
-                    d += 1;
-                }
+    fn galaxy(&self, index: usize) -> Result<&Point, String> {
+        self.galaxies.get(index).ok_or(format!("Galaxy {} not found, there are {} galaxies", index + 1, self.galaxies.len()))
+    }
+
+    fn distance(&self, a: usize, b: usize, expansion: u64) -> Result<Option<u64>, String> {
+        self.galaxy(a)?;
+        self.galaxy(b)?;
+        Ok(self.expanded(expansion).and_then(|expanded| Self::manhattan(expanded[a], expanded[b])))
+    }
+
+    fn manhattan(a: (u64, u64), b: (u64, u64)) -> Option<u64> {
+        a.0.abs_diff(b.0).checked_add(a.1.abs_diff(b.1))
+    }
+
+    fn positions(empty: &[bool], expansion: u64) -> Option<Vec<u64>> {
+        let mut positions = Vec::new();
+        let mut position: u64 = 0;
+        for e in empty {
+            positions.push(position);
+            position = position.checked_add(if *e { expansion } else { 1 })?;
+        }
+        Some(positions)
+    }
+
+    fn expanded(&self, expansion: u64) -> Option<Vec<(u64, u64)>> {
+        let rows = Self::positions(&self.row_empty, expansion)?;
+        let cols = Self::positions(&self.col_empty, expansion)?;
+        Some(self.galaxies.iter().map(|g| (rows[g.r], cols[g.c])).collect())
+    }
+
+    fn distance_matrix(&self, expansion: u64) -> Option<Vec<Vec<u64>>> {
+        let expanded = self.expanded(expansion)?;
+        let mut matrix = Vec::new();
+        for a in &expanded {
+            let mut row = Vec::new();
+            for b in &expanded {
+                row.push(Self::manhattan(*a, *b)?);
             }
+            matrix.push(row);
         }
-        if b.c > a.c {
-            for c in a.c..b.c {
-                if self.col_empty[c] {
-                    d += 1000000;
-                } else {
-                    d += 1;
+        Some(matrix)
+    }
+
+    fn nearest_neighbours(&self, expansion: u64) -> Option<Vec<Option<(usize, u64)>>> {
+        let expanded = self.expanded(expansion)?;
+        // galaxies are already in row order, so scan outwards from each one until the rows alone are further than the nearest so far
+        let mut nearest = Vec::new();
+        for (a, position) in expanded.iter().enumerate() {
+            let mut best: Option<(u64, usize)> = None;
+            let sides: [Box<dyn Iterator<Item = usize>>; 2] = [Box::new((0..a).rev()), Box::new(a + 1..expanded.len())];
+            for side in sides {
+                for b in side {
+                    if best.is_some_and(|(d, _)| position.0.abs_diff(expanded[b].0) > d) {
+                        break;
+                    }
+                    let d = Self::manhattan(*position, expanded[b])?;
+                    if best.is_none_or(|best| (d, b) < best) {
+                        best = Some((d, b));
+                    }
                 }
             }
-        } else {
-            for c in b.c..a.c {
-                if self.col_empty[c] {
-                    d += 1000000;
-                } else {
-                    d += 1;
-                }
+            nearest.push(best.map(|(d, b)| (b, d)));
+        }
+        Some(nearest)
+    }
+
+    fn distance_csv(&self, expansion: u64) -> Option<String> {
+        let matrix = self.distance_matrix(expansion)?;
+        let mut csv = String::from("galaxy");
+        for b in 0..matrix.len() {
+            csv.push_str(&format!(",{}", b + 1));
+        }
+        csv.push('\n');
+        for (a, row) in matrix.iter().enumerate() {
+            csv.push_str(&(a + 1).to_string());
+            for d in row {
+                csv.push_str(&format!(",{}", d));
             }
+            csv.push('\n');
         }
-        d
+        Some(csv)
     }
 
-    fn pairs(&self) -> Vec<(&Point, &Point)> {
-        let mut pairs = Vec::new();
-        for i in 0..self.galaxies.len() {
-            for j in (i+1)..self.galaxies.len() {
-                pairs.push((&self.galaxies[i], &self.galaxies[j]));
+    fn total_distance(&self, expansion: u64) -> Option<u64> {
+        let mut row_counts = vec![0; self.row_empty.len()];
+        let mut col_counts = vec![0; self.col_empty.len()];
+        for g in &self.galaxies {
+            row_counts[g.r] += 1;
+            col_counts[g.c] += 1;
+        }
+        let rows = Self::axis_distance(&row_counts, &self.row_empty, expansion)?;
+        let cols = Self::axis_distance(&col_counts, &self.col_empty, expansion)?;
+        u64::try_from(rows.checked_add(cols)?).ok()
+    }
+
+    fn axis_distance(counts: &[u128], empty: &[bool], expansion: u64) -> Option<u128> {
+        // walking along the axis, each galaxy is this far from every galaxy already seen:
+        // (seen * position) - (sum of seen positions)
+        let mut position: u128 = 0;
+        let mut seen: u128 = 0;
+        let mut seen_positions: u128 = 0;
+        let mut total: u128 = 0;
+        for (count, empty) in counts.iter().zip(empty) {
+            if *count > 0 {
+                let d = seen.checked_mul(position)?.checked_sub(seen_positions)?;
+                total = total.checked_add(count.checked_mul(d)?)?;
+                seen += count;
+                seen_positions = seen_positions.checked_add(count.checked_mul(position)?)?;
             }
+            position = position.checked_add(if *empty { expansion as u128 } else { 1 })?;
         }
-        pairs
+        Some(total)
     }
 }
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() == 2 {
+    let (flags, args): (Vec<String>, Vec<String>) = env::args().partition(|a| a.starts_with("--"));
+    if args.len() >= 2 {
         let filename = &args[1];
-        let text = fs::read_to_string(&filename)
-            .expect(&format!("Error reading from {}", filename));
-        let raw: Raw = text.parse().unwrap();
+        let text = fs::read_to_string(filename)
+            .unwrap_or_else(|_| panic!("Error reading from {}", filename));
+        let ragged = if flags.iter().any(|f| f == "--pad") { Ragged::Pad } else { Ragged::Reject };
+        let raw = Raw::parse(&text, ragged).unwrap();
         let image = Image::from(raw);
-        let mut sum = 0;
-        for (a, b) in image.pairs() {
-            let d = image.distance(a, b);
-            //println!("Distance: {}", d);
-            sum += d;
+        let expansions: Vec<u64> = if args.len() > 2 {
+            args[2..].iter().map(|a| a.parse().unwrap_or_else(|_| panic!("Invalid expansion factor: {}", a))).collect()
+        } else {
+            vec![2, 1000000]
+        };
+        if flags.iter().any(|f| f == "--csv") {
+            // a single matrix, so the expansion factor must be chosen explicitly
+            if args.len() != 3 {
+                println!("Please provide exactly 1 expansion factor with --csv");
+                return;
+            }
+            print!("{}", image.distance_csv(expansions[0]).expect("Distance overflowed u64"));
+            return;
+        }
+        for expansion in expansions {
+            match image.total_distance(expansion) {
+                Some(sum) => println!("Total (x{}): {}", expansion, sum),
+                None => println!("Total (x{}): overflowed u64", expansion)
+            }
+            for flag in &flags {
+                if let Some(pair) = flag.strip_prefix("--between=") {
+                    // galaxies are numbered from 1, like the puzzle
+                    let (a, b) = pair.split_once(',').expect("Expected --between=A,B");
+                    let a: usize = a.parse().ok().filter(|a| *a > 0).expect("Invalid galaxy number");
+                    let b: usize = b.parse().ok().filter(|b| *b > 0).expect("Invalid galaxy number");
+                    match image.distance(a - 1, b - 1, expansion).unwrap() {
+                        Some(d) => println!("  Between galaxy {} and galaxy {}: {}", a, b, d),
+                        None => println!("  Between galaxy {} and galaxy {}: overflowed u64", a, b)
+                    }
+                } else if flag == "--nearest" {
+                    for (a, nearest) in image.nearest_neighbours(expansion).expect("Distance overflowed u64").into_iter().enumerate() {
+                        match nearest {
+                            Some((b, d)) => println!("  Nearest to galaxy {}: galaxy {} at {}", a + 1, b + 1, d),
+                            None => println!("  Nearest to galaxy {}: none", a + 1)
+                        }
+                    }
+                }
+            }
         }
-        println!("Total: {}", sum);
     } else {
-        println!("Please provide 1 argument: Filename");
+        println!("Please provide at least 1 argument: Filename [Expansion...] [--between=A,B] [--nearest] [--pad] | Filename Expansion --csv [--pad]");
     }
 }
@@ -11,17 +11,44 @@ enum Character {
     Symbol(char)
 }
 
+struct SymbolMatch<'a> {
+    row: usize,
+    column: usize,
+    symbol: char,
+    numbers: Vec<&'a Number>
+}
+
+struct Graph {
+    numbers: usize,
+    symbols: Vec<(usize, usize, char)>,
+    edges: Vec<(usize, usize)>,
+    // the symbols touching each number
+    adjacency: Vec<Vec<usize>>
+}
+
 struct Number {
     row: usize,
     from_column: usize,
     to_column: usize,
-    value: u32
+    value: u64
+}
+
+#[derive(Copy, Clone)]
+enum Ragged {
+    Pad,
+    Reject
 }
 
 impl FromStr for Schematic {
     type Err = String;
 
     fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Self::parse(text, Ragged::Reject)
+    }
+}
+
+impl Schematic {
+    fn parse(text: &str, ragged: Ragged) -> Result<Self, String> {
         let mut rows = Vec::new();
         for l in text.lines() {
             let mut row = Vec::new();
@@ -30,6 +57,15 @@ impl FromStr for Schematic {
             }
             rows.push(row);
         }
+        let columns = rows.iter().map(|r| r.len()).max().unwrap_or(0);
+        for (r, row) in rows.iter_mut().enumerate() {
+            if row.len() != columns {
+                match ragged {
+                    Ragged::Pad => row.resize_with(columns, || Character::None),
+                    Ragged::Reject => return Err(format!("Row {} has {} columns, expected {}", r + 1, row.len(), columns))
+                }
+            }
+        }
         Ok(Self(rows))
     }
 }
@@ -52,21 +88,22 @@ impl Schematic {
     }
 
     fn columns(&self) -> usize {
-        self.0[0].len()
+        self.0.first().map_or(0, |row| row.len())
     }
 
-    fn find_numbers(&self) -> Vec<Number> {
+    fn find_numbers(&self) -> Result<Vec<Number>, String> {
         let mut numbers = Vec::new();
         for r in 0..self.rows() {
             let mut start = None;
-            let mut value: u32 = 0;
+            let mut value: u64 = 0;
             for c in 0..self.columns() {
                 if let Character::Digit(digit) = self.0[r][c] {
-                    if start.is_none() {
-                        start = Some(c);
-                        value = digit as u32;
+                    if let Some(s) = start {
+                        value = value.checked_mul(10).and_then(|v| v.checked_add(digit as u64))
+                            .ok_or(format!("Number at ({}, {}..{}) is too large for u64", r, s, c))?;
                     } else {
-                        value = value * 10 + digit as u32;
+                        start = Some(c);
+                        value = digit as u64;
                     }
                 } else {
                     if let Some(s) = start {
@@ -89,7 +126,7 @@ impl Schematic {
                 });
             }
         }
-        numbers
+        Ok(numbers)
     }
 
     fn contains_symbol(&self, min_r: usize, max_r: usize, min_c: usize, max_c: usize) -> bool {
@@ -103,22 +140,81 @@ impl Schematic {
         false
     }
 
-    fn find_gear_ratios(&self, parts: &Vec<Number>) -> Vec<u32> {
-        let mut gears = Vec::new();
+    fn index(&self, numbers: &[Number]) -> Vec<Vec<Option<usize>>> {
+        let mut index = vec![vec![None; self.columns()]; self.rows()];
+        for (i, n) in numbers.iter().enumerate() {
+            for cell in &mut index[n.row][n.from_column..(n.to_column + 1)] {
+                *cell = Some(i);
+            }
+        }
+        index
+    }
+
+    fn adjacent_numbers(&self, index: &[Vec<Option<usize>>], r: usize, c: usize) -> Vec<usize> {
+        let mut adjacent = Vec::new();
+        for row in &index[r.saturating_sub(1)..(r + 2).min(self.rows())] {
+            for i in row[c.saturating_sub(1)..(c + 2).min(self.columns())].iter().flatten() {
+                if !adjacent.contains(i) {
+                    adjacent.push(*i);
+                }
+            }
+        }
+        adjacent
+    }
+
+    fn graph(&self, numbers: &[Number]) -> Graph {
+        let index = self.index(numbers);
+        let mut symbols = Vec::new();
+        let mut edges = Vec::new();
+        let mut adjacency = vec![Vec::new(); numbers.len()];
         for r in 0..self.rows() {
             for c in 0..self.columns() {
                 if let Character::Symbol(symbol) = self.0[r][c] {
-                    if symbol == '*' {
-                        let adjacent: Vec<&Number> = parts.iter().filter(|p| p.is_adjacent_to(r, c)).collect();
-                        if adjacent.len() == 2 {
-                            let ratio: u32 = adjacent.iter().map(|p| p.value).product();
-                            gears.push(ratio);
+                    for n in self.adjacent_numbers(&index, r, c) {
+                        edges.push((n, symbols.len()));
+                        adjacency[n].push(symbols.len());
+                    }
+                    symbols.push((r, c, symbol));
+                }
+            }
+        }
+        Graph {
+            numbers: numbers.len(),
+            symbols,
+            edges,
+            adjacency
+        }
+    }
+
+    fn find_symbols<'a>(&self, numbers: &'a [Number], symbols: &[char], predicate: impl Fn(usize) -> bool) -> Vec<SymbolMatch<'a>> {
+        let index = self.index(numbers);
+        let mut matches = Vec::new();
+        for r in 0..self.rows() {
+            for c in 0..self.columns() {
+                if let Character::Symbol(symbol) = self.0[r][c] {
+                    if symbols.contains(&symbol) {
+                        let adjacent = self.adjacent_numbers(&index, r, c);
+                        if predicate(adjacent.len()) {
+                            matches.push(SymbolMatch {
+                                row: r,
+                                column: c,
+                                symbol,
+                                numbers: adjacent.into_iter().map(|i| &numbers[i]).collect()
+                            });
                         }
                     }
                 }
             }
         }
-        gears
+        matches
+    }
+
+    fn find_gear_ratios(&self, numbers: &[Number]) -> Vec<u128> {
+        // any number touching a gear is a part, so there is no need to filter for parts first
+        // the product of 2 u64 numbers always fits in a u128
+        self.find_symbols(numbers, &['*'], |count| count == 2).iter()
+            .map(|m| m.numbers.iter().map(|p| p.value as u128).product())
+            .collect()
     }
 }
 
@@ -130,31 +226,119 @@ impl Number {
         let max_c = if self.to_column == schematic.columns() - 1 { self.to_column } else { self.to_column + 1 };
         schematic.contains_symbol(min_r, max_r, min_c, max_c)
     }
+}
+
+impl Graph {
+    fn symbols_of(&self, number: usize) -> &[usize] {
+        &self.adjacency[number]
+    }
+
+    fn isolated(&self) -> Vec<usize> {
+        (0..self.numbers).filter(|n| self.adjacency[*n].is_empty()).collect()
+    }
+
+    fn to_dot(&self, numbers: &[Number]) -> String {
+        let mut dot = String::from("graph schematic {\n");
+        for (i, n) in numbers.iter().enumerate() {
+            dot.push_str(&format!("    n{} [label=\"{}\" shape=box];\n", i, n.value));
+        }
+        for (i, (r, c, symbol)) in self.symbols.iter().enumerate() {
+            dot.push_str(&format!("    s{} [label=\"{} ({}, {})\" shape=circle];\n", i, dot_escape(*symbol), r, c));
+        }
+        for (n, s) in &self.edges {
+            dot.push_str(&format!("    n{} -- s{};\n", n, s));
+        }
+        dot.push_str("}\n");
+        dot
+    }
 
-    fn is_adjacent_to(&self, row: usize, column: usize) -> bool {
-        row.abs_diff(self.row) <= 1 && column as isize >= self.from_column as isize - 1 && column <= self.to_column + 1
+    fn to_json(&self, numbers: &[Number]) -> String {
+        let numbers: Vec<String> = numbers.iter().enumerate().map(|(i, n)| {
+            format!("{{\"id\":{},\"value\":{},\"row\":{},\"from_column\":{},\"to_column\":{}}}", i, n.value, n.row, n.from_column, n.to_column)
+        }).collect();
+        let symbols: Vec<String> = self.symbols.iter().enumerate().map(|(i, (r, c, symbol))| {
+            format!("{{\"id\":{},\"symbol\":\"{}\",\"row\":{},\"column\":{}}}", i, json_escape(*symbol), r, c)
+        }).collect();
+        let edges: Vec<String> = self.edges.iter().map(|(n, s)| format!("{{\"number\":{},\"symbol\":{}}}", n, s)).collect();
+        format!("{{\"numbers\":[{}],\"symbols\":[{}],\"edges\":[{}]}}\n", numbers.join(","), symbols.join(","), edges.join(","))
+    }
+}
+
+fn dot_escape(c: char) -> String {
+    match c {
+        '"' | '\\' => format!("\\{}", c),
+        _ => c.to_string()
+    }
+}
+
+fn json_escape(c: char) -> String {
+    match c {
+        '"' | '\\' => format!("\\{}", c),
+        c if c.is_control() => format!("\\u{:04x}", c as u32),
+        _ => c.to_string()
     }
 }
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let (flags, args): (Vec<String>, Vec<String>) = env::args().partition(|a| a.starts_with("--"));
     if args.len() == 2 {
         let filename = &args[1];
-        let text = fs::read_to_string(&filename)
-            .expect(&format!("Error reading from {}", filename));
-        let schematic: Schematic = text.parse().unwrap();
+        let text = fs::read_to_string(filename)
+            .unwrap_or_else(|_| panic!("Error reading from {}", filename));
+        let ragged = if flags.iter().any(|f| f == "--pad") { Ragged::Pad } else { Ragged::Reject };
+        let schematic = Schematic::parse(&text, ragged).unwrap();
+        let numbers = schematic.find_numbers().unwrap();
+        if flags.iter().any(|f| f == "--graph=dot" || f == "--graph=json") {
+            let graph = schematic.graph(&numbers);
+            if flags.iter().any(|f| f == "--graph=dot") {
+                print!("{}", graph.to_dot(&numbers));
+            } else {
+                print!("{}", graph.to_json(&numbers));
+            }
+            return;
+        }
         println!("Dimensions: {}x{}", schematic.rows(), schematic.columns());
-        let numbers = schematic.find_numbers();
         println!("Numbers: {}", numbers.len());
-        let parts: Vec<Number> = numbers.into_iter().filter(|n| n.is_part_of(&schematic)).collect();
+        let parts: Vec<&Number> = numbers.iter().filter(|n| n.is_part_of(&schematic)).collect();
         println!("Parts: {}", parts.len());
-        let parts_sum: u32 = parts.iter().map(|p| p.value).sum();
+        let parts_sum = parts.iter().try_fold(0u128, |sum, p| sum.checked_add(p.value as u128)).expect("Parts sum overflowed u128");
         println!("Parts sum: {}", parts_sum);
-        let gears = schematic.find_gear_ratios(&parts);
+        if flags.iter().any(|f| f == "--explain") {
+            let graph = schematic.graph(&numbers);
+            for (i, n) in numbers.iter().enumerate() {
+                let symbols: Vec<String> = graph.symbols_of(i).iter().map(|s| {
+                    let (r, c, symbol) = graph.symbols[*s];
+                    format!("{} at ({}, {})", symbol, r, c)
+                }).collect();
+                if !symbols.is_empty() {
+                    println!("{} at ({}, {}..{}) touches {}", n.value, n.row, n.from_column, n.to_column, symbols.join(", "));
+                }
+            }
+            for i in graph.isolated() {
+                let n = &numbers[i];
+                println!("{} at ({}, {}..{}) is isolated", n.value, n.row, n.from_column, n.to_column);
+            }
+        }
+        for flag in &flags {
+            if let Some(query) = flag.strip_prefix("--symbols=") {
+                // --symbols=*#:2 finds every * or # touching exactly 2 numbers
+                let (symbols, count) = query.rsplit_once(':').unwrap_or((query, "1.."));
+                let symbols: Vec<char> = symbols.chars().collect();
+                let predicate = |n: usize| match count.strip_suffix("..") {
+                    Some(min) => n >= min.parse().expect("Invalid count"),
+                    None => n == count.parse::<usize>().expect("Invalid count")
+                };
+                for m in schematic.find_symbols(&numbers, &symbols, predicate) {
+                    let values: Vec<u64> = m.numbers.iter().map(|n| n.value).collect();
+                    println!("{} at ({}, {}): {:?}", m.symbol, m.row, m.column, values);
+                }
+            }
+        }
+        let gears = schematic.find_gear_ratios(&numbers);
         println!("Gears: {:?}", gears);
-        let gears_sum: u32 = gears.iter().sum();
+        let gears_sum = gears.iter().try_fold(0u128, |sum, g| sum.checked_add(*g)).expect("Gears sum overflowed u128");
         println!("Gears sum: {}", gears_sum);
     } else {
-        println!("Please provide 1 argument: Filename");
+        println!("Please provide 1 argument: Filename [--pad] [--symbols=SYMBOLS:COUNT] [--explain] [--graph=dot|json]");
     }
 }
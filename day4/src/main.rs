@@ -2,9 +2,7 @@ use std::collections::HashSet;
 use std::fs;
 use std::env;
 use std::str::FromStr;
-use std::collections::VecDeque;
 
-#[derive(Clone)]
 struct Card {
     number: usize,
     winning: HashSet<u32>,
@@ -27,6 +25,62 @@ impl Card {
     }
 }
 
+fn copies(cards: &[Card]) -> Result<Vec<u64>, String> {
+    // each card adds its copies to the next `wins` cards, tracked as a running total of differences
+    let mut copies = vec![0; cards.len()];
+    let mut change = vec![0i128; cards.len() + 1];
+    let mut current: i128 = 1;
+    for (i, card) in cards.iter().enumerate() {
+        current += change[i];
+        copies[i] = u64::try_from(current).map_err(|_| format!("Copies of card {} overflowed u64", card.number))?;
+        let wins = card.wins();
+        if i + wins >= cards.len() {
+            return Err(format!("Card {} wins {} cards, but there are only {} cards after it", card.number, wins, cards.len() - i - 1));
+        }
+        change[i + 1] += current;
+        change[i + wins + 1] -= current;
+    }
+    Ok(copies)
+}
+
+fn parse_numbers(list: &str) -> Result<HashSet<u32>, String> {
+    let mut numbers = HashSet::new();
+    for s in list.split(" ").filter(|s| !s.is_empty()) {
+        let n = s.parse().map_err(|_| format!("Invalid number: {}", s))?;
+        if !numbers.insert(n) {
+            return Err(format!("Duplicate number: {}", n));
+        }
+    }
+    Ok(numbers)
+}
+
+fn validate(cards: &[Card]) -> Result<(), String> {
+    // copies are won by position, so card numbers must match their position, which also rules out duplicates and gaps
+    for (i, card) in cards.iter().enumerate() {
+        if card.number != i + 1 {
+            return Err(format!("Card {} found at position {}, expected card {} (cards must be numbered in order, without duplicates or gaps)", card.number, i + 1, i + 1));
+        }
+    }
+    Ok(())
+}
+
+fn ledger(cards: &[Card], copies: &[u64], csv: bool) -> String {
+    let mut text = String::new();
+    if csv {
+        text.push_str("card,wins,points,copies\n");
+    } else {
+        text.push_str(&format!("{:>6} {:>6} {:>12} {:>20}\n", "Card", "Wins", "Points", "Copies"));
+    }
+    for (card, count) in cards.iter().zip(copies) {
+        if csv {
+            text.push_str(&format!("{},{},{},{}\n", card.number, card.wins(), card.points(), count));
+        } else {
+            text.push_str(&format!("{:>6} {:>6} {:>12} {:>20}\n", card.number, card.wins(), card.points(), count));
+        }
+    }
+    text
+}
+
 impl FromStr for Card {
     type Err = String;
 
@@ -35,13 +89,14 @@ impl FromStr for Card {
         if sections.len() != 2 {
             return Err(format!("Expected 2 sections, found {}", sections.len()));
         }
-        let number = sections[0].split(" ").last().unwrap().trim().parse().unwrap();
+        let number: usize = sections[0].split(" ").last().unwrap().trim().parse()
+            .map_err(|_| format!("Invalid card number: {}", sections[0]))?;
         let lists: Vec<&str> = sections[1].split("|").collect();
         if lists.len() != 2 {
             return Err(format!("Expected 2 lists, found {}", lists.len()));
         }
-        let winning = lists[0].split(" ").filter(|s| s.len() > 0).map(|s| s.parse().unwrap()).collect();
-        let have = lists[1].split(" ").filter(|s| s.len() > 0).map(|s| s.parse().unwrap()).collect();
+        let winning = parse_numbers(lists[0]).map_err(|e| format!("Card {} winning numbers: {}", number, e))?;
+        let have = parse_numbers(lists[1]).map_err(|e| format!("Card {} held numbers: {}", number, e))?;
         Ok(Self {
             number,
             winning,
@@ -51,25 +106,26 @@ impl FromStr for Card {
 }
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let (flags, args): (Vec<String>, Vec<String>) = env::args().partition(|a| a.starts_with("--"));
     if args.len() == 2 {
         let filename = &args[1];
-        let text = fs::read_to_string(&filename)
-            .expect(&format!("Error reading from {}", filename));
+        let text = fs::read_to_string(filename)
+            .unwrap_or_else(|_| panic!("Error reading from {}", filename));
         let cards: Vec<Card> = text.lines().map(|s| s.parse().unwrap()).collect();
+        validate(&cards).unwrap();
+        let copies = copies(&cards).unwrap();
+        if flags.iter().any(|f| f == "--ledger=csv") {
+            print!("{}", ledger(&cards, &copies, true));
+            return;
+        } else if flags.iter().any(|f| f == "--ledger") {
+            print!("{}", ledger(&cards, &copies, false));
+        }
         let total: usize = cards.iter().map(Card::points).sum();
         println!("Total points: {}", total);
         // part2
-        let mut scratchcards = 0;
-        let mut remaining: VecDeque<Card> = cards.iter().map(|c| c.clone()).collect();
-        while let Some(card) = remaining.pop_front() {
-            scratchcards += 1;
-            for i in card.number..(card.number + card.wins()) {
-                remaining.push_back(cards[i].clone());
-            }
-        }
+        let scratchcards = copies.iter().try_fold(0u64, |sum, c| sum.checked_add(*c)).expect("Total scratchcards overflowed u64");
         println!("Total scratchcards: {}", scratchcards);
     } else {
-        println!("Please provide 1 argument: Filename");
+        println!("Please provide 1 argument: Filename [--ledger|--ledger=csv]");
     }
 }
@@ -1,71 +1,220 @@
 use std::fs;
 use std::env;
+use std::str::FromStr;
 
 struct Race {
-    time: usize,
-    distance: usize
+    time: u128,
+    distance: u128
 }
 
-fn parse(text: &str) -> Vec<Race> {
-    let lines: Vec<&str> = text.lines().collect();
+#[derive(Debug)]
+enum BoatModel {
+    Linear,
+    Quadratic,
+    Capped(u128),
+    Drag(f64)
+}
+
+#[derive(PartialEq, PartialOrd)]
+enum Distance {
+    Exact(u128),
+    Approximate(f64)
+}
+
+impl FromStr for BoatModel {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        // linear, quadratic, cap:10, drag:0.01
+        let (name, parameter) = text.split_once(':').unwrap_or((text, ""));
+        match name {
+            "linear" => Ok(Self::Linear),
+            "quadratic" => Ok(Self::Quadratic),
+            "cap" => Ok(Self::Capped(parameter.parse().map_err(|_| format!("Invalid cap: {}", parameter))?)),
+            "drag" => {
+                let drag: f64 = parameter.parse().map_err(|_| format!("Invalid drag: {}", parameter))?;
+                if drag > 0.0 && drag < 1.0 {
+                    Ok(Self::Drag(drag))
+                } else {
+                    Err(format!("Drag must be between 0 and 1, found {}", drag))
+                }
+            },
+            _ => Err(format!("Unknown boat model: {}", name))
+        }
+    }
+}
+
+impl BoatModel {
+    fn distance(&self, time: u128, hold: u128) -> Distance {
+        let moving = time - hold;
+        // saturates rather than overflowing, since that is further than any u128 record anyway
+        let exact = |speed: Option<u128>| Distance::Exact(speed.and_then(|s| s.checked_mul(moving)).unwrap_or(u128::MAX));
+        match self {
+            Self::Linear => exact(Some(hold)),
+            Self::Quadratic => exact(hold.checked_mul(hold)),
+            Self::Capped(cap) => exact(Some(hold.min(*cap))),
+            Self::Drag(drag) => {
+                // speed drops by a fraction each millisecond, so the distance is a geometric series,
+                // which can never be further than without drag, however the rounding falls
+                let distance = hold as f64 * (1.0 - (1.0 - drag).powf(moving as f64)) / drag;
+                Distance::Approximate(distance.min(hold as f64 * moving as f64))
+            }
+        }
+    }
+}
+
+impl Distance {
+    fn beats(&self, record: u128) -> bool {
+        match self {
+            Self::Exact(distance) => *distance > record,
+            // a fractional distance beats the record if it is strictly further, so compare without rounding
+            Self::Approximate(distance) => *distance > record as f64
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+enum Mode {
+    Columns,
+    Kerned
+}
+
+fn parse(text: &str, mode: Mode) -> Result<Vec<Race>, String> {
+    let lines: Vec<&str> = text.lines().filter(|l| !l.trim().is_empty()).collect();
     if lines.len() != 2 {
-        panic!("Expected 2 lines, found {}", lines.len());
+        return Err(format!("Expected 2 lines, found {}", lines.len()));
     }
-    let times: Vec<usize> = lines[0].split(":").nth(1).unwrap().split(" ").filter(|s| s.len() > 0).map(|s| s.parse().unwrap()).collect();
-    let distances: Vec<usize> = lines[1].split(":").nth(1).unwrap().split(" ").filter(|s| s.len() > 0).map(|s| s.parse().unwrap()).collect();
+    let times = parse_values(lines[0], "Time", mode)?;
+    let distances = parse_values(lines[1], "Distance", mode)?;
     if times.len() != distances.len() {
-        panic!("Distance and time dont match");
+        return Err(format!("Found {} times but {} distances", times.len(), distances.len()));
     }
-    let mut races = Vec::new();
-    for i in 0..times.len() {
-        races.push(Race {
-            distance: distances[i],
-            time: times[i]
-        });
+    Ok(times.into_iter().zip(distances).map(|(time, distance)| Race { time, distance }).collect())
+}
+
+fn parse_values(line: &str, label: &str, mode: Mode) -> Result<Vec<u128>, String> {
+    // Time:      7  15   30
+    let (found, values) = line.split_once(':').ok_or(format!("Expected '{}:' in line: {}", label, line))?;
+    if found.trim() != label {
+        return Err(format!("Expected label {}, found {}", label, found.trim()));
     }
-    races
+    let columns: Vec<&str> = values.split_whitespace().collect();
+    let numbers: Vec<String> = match mode {
+        Mode::Columns => columns.iter().map(|c| c.to_string()).collect(),
+        Mode::Kerned if columns.is_empty() => Vec::new(),
+        // the spaces are just bad kerning, so it is really one number
+        Mode::Kerned => vec![columns.concat()]
+    };
+    numbers.iter().map(|n| n.parse().map_err(|e| format!("Invalid {} value {}: {}", label, n, e))).collect()
 }
 
 impl Race {
-    fn min_to_beat(&self) -> usize {
-        for i in 0..self.time {
-            let distance = i * (self.time - i);
-            if distance > self.distance {
-                return i;
+    fn beats(&self, hold: u128) -> bool {
+        // if the distance overflows it must be further than any u128 record
+        hold <= self.time && hold.checked_mul(self.time - hold).is_none_or(|d| d > self.distance)
+    }
+
+    fn min_to_beat(&self) -> Option<u128> {
+        // the best hold time is half the race, so if that can't win nothing can
+        if !self.beats(self.time / 2) {
+            return None;
+        }
+        // hold * (time - hold) > distance, so the smallest hold is just above (time - sqrt(time^2 - 4 * distance)) / 2
+        let estimate = self.time.checked_mul(self.time)
+            .and_then(|t2| t2.checked_sub(self.distance.checked_mul(4)?))
+            .map(|discriminant| (self.time - discriminant.isqrt()) / 2);
+        let mut hold = match estimate {
+            Some(estimate) => estimate,
+            None => {
+                // too large to square, so binary search instead
+                let mut low = 0;
+                let mut high = self.time / 2;
+                while low < high {
+                    let mid = low + (high - low) / 2;
+                    if self.beats(mid) {
+                        high = mid;
+                    } else {
+                        low = mid + 1;
+                    }
+                }
+                low
             }
+        };
+        // integer square root rounds down, so nudge onto the exact boundary
+        while hold > 0 && self.beats(hold - 1) {
+            hold -= 1;
         }
-        panic!("Could not min beat");
+        while !self.beats(hold) {
+            hold += 1;
+        }
+        Some(hold)
+    }
+
+    fn max_to_beat(&self) -> Option<u128> {
+        // distance is symmetric around half the race
+        self.min_to_beat().map(|min| self.time - min)
     }
 
-    fn max_to_beat(&self) -> usize {
-        for i in (0..self.time).rev() {
-            let distance = i * (self.time - i);
-            if distance > self.distance {
-                return i;
+    fn ways_to_beat(&self, model: &BoatModel) -> u128 {
+        if let BoatModel::Linear = model {
+            return match (self.min_to_beat(), self.max_to_beat()) {
+                (Some(min), Some(max)) => max - min + 1,
+                _ => 0
+            };
+        }
+        // no closed form, so search numerically, assuming distance rises to a single peak then falls
+        let distance = |hold| model.distance(self.time, hold);
+        let beats = |hold| distance(hold).beats(self.distance);
+        let mut low = 0;
+        let mut high = self.time;
+        while high - low > 2 {
+            let third = (high - low) / 3;
+            if distance(low + third) < distance(high - third) {
+                low += third + 1;
+            } else {
+                high -= third;
             }
         }
-        panic!("Could not max beat");
+        // a model only ever gives one kind of distance, and never NaN, so they are always comparable
+        let peak = (low..=high).max_by(|a, b| distance(*a).partial_cmp(&distance(*b)).unwrap()).unwrap();
+        if !beats(peak) {
+            return 0;
+        }
+        let first = Self::boundary(0, peak, beats);
+        let last = Self::boundary(peak, self.time + 1, |h| !beats(h)) - 1;
+        last - first + 1
     }
 
-    fn ways_to_beat(&self) -> usize {
-        self.max_to_beat() - self.min_to_beat() + 1
+    fn boundary(mut low: u128, mut high: u128, found: impl Fn(u128) -> bool) -> u128 {
+        // the first value in low..high where found is true, or high if there are none
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if found(mid) {
+                high = mid;
+            } else {
+                low = mid + 1;
+            }
+        }
+        low
     }
 }
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let (flags, args): (Vec<String>, Vec<String>) = env::args().partition(|a| a.starts_with("--"));
     if args.len() == 2 {
         let filename = &args[1];
-        let text = fs::read_to_string(&filename)
-            .expect(&format!("Error reading from {}", filename));
-        let mut races = parse(&text);
-        let mut product: usize = races.iter().map(|r| r.ways_to_beat()).product::<usize>();
-        println!("Separate product: {}", product);
-        let text_no_spaces: String = text.chars().filter(|c| *c != ' ').collect();
-        races = parse(&text_no_spaces);
-        product = races.iter().map(|r| r.ways_to_beat()).product::<usize>();
-        println!("Combined product: {}", product);
+        let text = fs::read_to_string(filename)
+            .unwrap_or_else(|_| panic!("Error reading from {}", filename));
+        let model: BoatModel = match flags.iter().find_map(|f| f.strip_prefix("--model=")) {
+            Some(model) => model.parse().unwrap(),
+            None => BoatModel::Linear
+        };
+        for (name, mode) in [("Separate", Mode::Columns), ("Combined", Mode::Kerned)] {
+            let races = parse(&text, mode).unwrap();
+            let product = races.iter().try_fold(1u128, |p, r| p.checked_mul(r.ways_to_beat(&model))).expect("Product overflowed u128");
+            println!("{} product: {}", name, product);
+        }
     } else {
-        println!("Please provide 1 argument: Filename");
+        println!("Please provide 1 argument: Filename [--model=linear|quadratic|cap:N|drag:F]");
     }
 }
@@ -4,19 +4,38 @@ use std::str::FromStr;
 use std::cmp::Ordering;
 use std::collections::HashMap;
 
-struct Set {
-    hands: Vec<Hand>
+struct Set<'a> {
+    hands: Vec<Hand<'a>>
 }
 
 #[derive(PartialEq, Eq, Debug)]
-struct Hand {
+struct Hand<'a> {
     cards: Vec<Card>,
-    bid: usize
+    bid: usize,
+    rules: &'a Rules,
+    strength: Strength
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug)]
+struct Strength {
+    hand_type: usize,
+    cards: Vec<u8>
+}
+
+#[derive(PartialEq, Eq, Debug)]
+struct Rules {
+    wild: Vec<Card>,
+    types: Vec<Type>
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct Type {
+    name: String,
+    signature: Vec<usize>
 }
 
 #[derive(Copy, Clone, Debug, PartialOrd, Ord, PartialEq, Eq, Hash)]
 enum Card {
-    Joker,
     Two,
     Three,
     Four,
@@ -26,118 +45,175 @@ enum Card {
     Eight,
     Nine,
     Ten,
+    Jack,
     Queen,
     King,
     Ace
 }
 
-impl FromStr for Set {
+impl Rules {
+    fn standard() -> Self {
+        Self::with_wild(Vec::new())
+    }
+
+    fn jokers_wild() -> Self {
+        Self::with_wild(vec![Card::Jack])
+    }
+
+    fn with_wild(wild: Vec<Card>) -> Self {
+        let mut rules = Self {
+            wild,
+            types: Vec::new()
+        };
+        rules.add_type(Type::new("High card", &[]));
+        rules.add_type(Type::new("One pair", &[2]));
+        rules.add_type(Type::new("Two pair", &[2, 2]));
+        rules.add_type(Type::new("Three of a kind", &[3]));
+        rules.add_type(Type::new("Full house", &[3, 2]));
+        rules.add_type(Type::new("Four of a kind", &[4]));
+        rules.add_type(Type::new("Five of a kind", &[5]));
+        rules
+    }
+
+    fn add_type(&mut self, new_type: Type) {
+        // ordering types by their signature ranks them like poker, eg. [2, 2] < [3] < [3, 2]
+        self.types.retain(|t| t.signature != new_type.signature);
+        self.types.push(new_type);
+        self.types.sort_by(|a, b| a.signature.cmp(&b.signature));
+    }
+
+    fn is_wild(&self, card: Card) -> bool {
+        self.wild.contains(&card)
+    }
+
+    fn strength(&self, card: Card) -> u8 {
+        // wild cards are weaker than all the others
+        if self.is_wild(card) {
+            card as u8
+        } else {
+            card as u8 + Card::Ace as u8 + 1
+        }
+    }
+}
+
+impl FromStr for Rules {
     type Err = String;
 
     fn from_str(text: &str) -> Result<Self, Self::Err> {
+        // standard, jokers, or the wild cards themselves, eg. J2
+        match text {
+            "standard" => Ok(Self::standard()),
+            "jokers" => Ok(Self::jokers_wild()),
+            "" => Err("Wild cards cannot be empty".to_string()),
+            _ => Ok(Self::with_wild(text.chars().map(|c| Card::from(c).map_err(|_| format!("Invalid wild card: {}", c))).collect::<Result<_, _>>()?))
+        }
+    }
+}
+
+impl<'a> Set<'a> {
+    fn parse(text: &str, rules: &'a Rules) -> Result<Self, String> {
         Ok(Self {
-            hands: text.lines().map(|s| s.parse().unwrap()).collect()
+            hands: text.lines().map(|s| Hand::parse(s, rules)).collect::<Result<_, _>>()?
         })
     }
 }
 
-impl FromStr for Hand {
-    type Err = String;
-
-    fn from_str(line: &str) -> Result<Self, Self::Err> {
+impl<'a> Hand<'a> {
+    fn parse(line: &str, rules: &'a Rules) -> Result<Self, String> {
         let words: Vec<&str> = line.split(" ").collect();
         if words.len() != 2 {
             return Err(format!("Expected 2 words, found {}", words.len()));
         }
+        let cards = words[0].chars().map(Card::from).collect::<Result<Vec<Card>, _>>()?;
+        let strength = Strength {
+            hand_type: rules.hand_type(&cards),
+            cards: cards.iter().map(|c| rules.strength(*c)).collect()
+        };
         Ok(Self {
-            cards: words[0].chars().map(|c| Card::from(c)).collect::<Vec<Card>>(),
-            bid: words[1].parse().unwrap()
+            cards,
+            bid: words[1].parse().map_err(|_| format!("Invalid bid: {}", words[1]))?,
+            rules,
+            strength
         })
     }
+
+    fn strength(&self) -> &Strength {
+        &self.strength
+    }
 }
 
-impl Hand {
-    fn group(&self) -> HashMap<Card, usize> {
-        let mut map = HashMap::new();
-        for card in &self.cards {
-            if let Some(existing) = map.get(card) {
-                map.insert(*card, existing + 1);
-            } else {
-                map.insert(*card, 1);
-            }
+fn group(cards: &[Card]) -> HashMap<Card, usize> {
+    let mut map = HashMap::new();
+    for card in cards {
+        if let Some(existing) = map.get(card) {
+            map.insert(*card, existing + 1);
+        } else {
+            map.insert(*card, 1);
         }
-        map
-    }
-}
-
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
-enum Type {
-    HighCard,
-    OnePair,
-    TwoPair,
-    ThreeOfAKind,
-    FullHouse,
-    FourOfAKind,
-    FiveOfAKind
-}
-
-impl Hand {
-    fn hand_type(&self) -> Type {
-        let mut g = self.group();
-        let jokers: usize = g.remove(&Card::Joker).unwrap_or(0);
-        match (g.len(), *g.values().max().unwrap_or(&0), jokers) {
-            (0, _, 5) => Type::FiveOfAKind,
-            (1, _, 0) => Type::FiveOfAKind,
-            (1, _, 1) => Type::FiveOfAKind,
-            (1, _, 2) => Type::FiveOfAKind,
-            (1, _, 3) => Type::FiveOfAKind,
-            (1, _, 4) => Type::FiveOfAKind,
-            (_, _, 4) => Type::FourOfAKind,
-            (2, 4, 0) => Type::FourOfAKind,
-            (2, 3, 1) => Type::FourOfAKind,
-            (2, 2, 2) => Type::FourOfAKind,
-            (2, 1, 3) => Type::FourOfAKind,
-            (2, 3, 0) => Type::FullHouse,
-            (2, 2, 1) => Type::FullHouse,
-            (2, 1, 2) => Type::FullHouse,
-            (3, 3, 0) => Type::ThreeOfAKind,
-            (3, 2, 1) => Type::ThreeOfAKind,
-            (3, 1, 2) => Type::ThreeOfAKind,
-            (3, 2, 0) => Type::TwoPair,
-            (3, 1, 1) => Type::TwoPair,
-            (4, 2, 0) => Type::OnePair,
-            (4, 1, 1) => Type::OnePair,
-            (5, _, _) => Type::HighCard,
-            _ => panic!("Type not found: {:?}", self.cards)
+    }
+    map
+}
+
+impl Type {
+    fn new(name: &str, signature: &[usize]) -> Self {
+        Self {
+            name: name.to_string(),
+            signature: signature.to_vec()
         }
     }
+
+    fn matches(&self, counts: &[usize], wild: usize) -> bool {
+        // pairing the largest groups with the largest counts in the signature needs the fewest wild cards to fill the shortfall,
+        // eg. [3, 2] matches a full house, or [2, 2] with 1 wild card
+        let shortfall: usize = self.signature.iter().enumerate().map(|(i, s)| s.saturating_sub(counts.get(i).copied().unwrap_or(0))).sum();
+        shortfall <= wild
+    }
+}
+
+impl FromStr for Type {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        // Two triples:3,3
+        let (name, signature) = text.split_once(':').ok_or(format!("Expected NAME:COUNTS, found {}", text))?;
+        let mut signature: Vec<usize> = signature.split(',').map(|c| c.trim().parse().map_err(|_| format!("Invalid count: {}", c))).collect::<Result<_, _>>()?;
+        signature.sort_by(|a, b| b.cmp(a));
+        Ok(Self::new(name, &signature))
+    }
+}
+
+impl Rules {
+    fn counts(&self, cards: &[Card]) -> (Vec<usize>, usize) {
+        let mut g = group(cards);
+        let wild: usize = self.wild.iter().map(|w| g.remove(w).unwrap_or(0)).sum();
+        let mut counts: Vec<usize> = g.into_values().collect();
+        counts.sort_by(|a, b| b.cmp(a));
+        (counts, wild)
+    }
+
+    fn hand_type(&self, cards: &[Card]) -> usize {
+        let (counts, wild) = self.counts(cards);
+        // the wild cards could be split across several groups, so take the best type they can reach,
+        // the empty signature matches every hand, so there is always a type
+        self.types.iter().rposition(|t| t.matches(&counts, wild)).unwrap_or(0)
+    }
 }
 
-impl PartialOrd for Hand {
+impl PartialOrd for Hand<'_> {
     fn partial_cmp(&self, other: &Hand) -> Option<std::cmp::Ordering> {
-        let type_cmp = self.hand_type().cmp(&other.hand_type());
-        if type_cmp != Ordering::Equal {
-            return Some(type_cmp);
-        }
-        for i in 0..self.cards.len() {
-            let card_cmp = self.cards[i].cmp(&other.cards[i]);
-            if card_cmp != Ordering::Equal {
-                return Some(card_cmp);
-            }
-        }
-        Some(self.bid.cmp(&other.bid))
+        Some(self.cmp(other))
     }
 }
 
-impl Ord for Hand {
+impl Ord for Hand<'_> {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(&other).unwrap()
+        self.strength().cmp(other.strength()).then(self.bid.cmp(&other.bid))
     }
 }
 
 impl Card {
-    fn from(c: char) -> Self {
-        match c {
+    fn from(c: char) -> Result<Self, String> {
+        Ok(match c {
             '2' => Self::Two,
             '3' => Self::Three,
             '4' => Self::Four,
@@ -147,20 +223,73 @@ impl Card {
             '8' => Self::Eight,
             '9' => Self::Nine,
             'T' => Self::Ten,
-            'J' => Self::Joker,
+            'J' => Self::Jack,
             'Q' => Self::Queen,
             'K' => Self::King,
             'A' => Self::Ace,
-            _ => panic!("Invalid card: {}", c)
+            _ => return Err(format!("Invalid card: {}", c))
+        })
+    }
+
+    fn to_char(self) -> char {
+        match self {
+            Self::Two => '2',
+            Self::Three => '3',
+            Self::Four => '4',
+            Self::Five => '5',
+            Self::Six => '6',
+            Self::Seven => '7',
+            Self::Eight => '8',
+            Self::Nine => '9',
+            Self::Ten => 'T',
+            Self::Jack => 'J',
+            Self::Queen => 'Q',
+            Self::King => 'K',
+            Self::Ace => 'A'
         }
     }
 }
 
-impl Set {
+struct Ranked {
+    rank: usize,
+    hand: String,
+    hand_type: String,
+    decided_by: String,
+    bid: usize,
+    winnings: usize
+}
+
+impl Hand<'_> {
+    fn decided_by(&self, below: &Hand) -> String {
+        // why this hand ranks above the one just below it
+        if self.strength.hand_type != below.strength.hand_type {
+            return "type".to_string();
+        }
+        for (i, (a, b)) in self.strength.cards.iter().zip(&below.strength.cards).enumerate() {
+            if a != b {
+                return format!("card {} ({} over {})", i + 1, self.cards[i].to_char(), below.cards[i].to_char());
+            }
+        }
+        "bid".to_string()
+    }
+}
+
+impl Set<'_> {
     fn sort(&mut self) {
         self.hands.sort();
     }
 
+    fn ranking(&self) -> Vec<Ranked> {
+        self.hands.iter().enumerate().map(|(i, hand)| Ranked {
+            rank: i + 1,
+            hand: hand.cards.iter().map(|c| c.to_char()).collect(),
+            hand_type: hand.rules.types[hand.strength.hand_type].name.clone(),
+            decided_by: if i == 0 { "-".to_string() } else { hand.decided_by(&self.hands[i - 1]) },
+            bid: hand.bid,
+            winnings: (i + 1) * hand.bid
+        }).collect()
+    }
+
     fn winnings(&self) -> usize {
         let mut total = 0;
         for i in 0..self.hands.len() {
@@ -170,16 +299,58 @@ impl Set {
     }
 }
 
+fn csv_field(text: &str) -> String {
+    // rules and type names come from the command line, so may need quoting
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let (flags, args): (Vec<String>, Vec<String>) = env::args().partition(|a| a.starts_with("--"));
     if args.len() == 2 {
         let filename = &args[1];
-        let text = fs::read_to_string(&filename)
-            .expect(&format!("Error reading from {}", filename));
-        let mut set: Set = text.parse().unwrap();
-        set.sort();
-        println!("Total winnings: {}", set.winnings());
+        let text = fs::read_to_string(filename)
+            .unwrap_or_else(|_| panic!("Error reading from {}", filename));
+        let mut all_rules = vec![("Standard", Rules::standard()), ("Jokers wild", Rules::jokers_wild())];
+        for flag in &flags {
+            if let Some(rules) = flag.strip_prefix("--rules=") {
+                all_rules.push((rules, rules.parse().unwrap()));
+            }
+        }
+        for flag in &flags {
+            if let Some(new_type) = flag.strip_prefix("--type=") {
+                let new_type: Type = new_type.parse().unwrap();
+                for (_, rules) in &mut all_rules {
+                    rules.add_type(new_type.clone());
+                }
+            }
+        }
+        let csv = flags.iter().any(|f| f == "--report=csv");
+        let table = flags.iter().any(|f| f == "--report");
+        if csv {
+            println!("rules,rank,hand,type,decided_by,bid,winnings");
+        }
+        for (name, rules) in &all_rules {
+            let mut set = Set::parse(&text, rules).unwrap();
+            set.sort();
+            if csv {
+                for r in set.ranking() {
+                    println!("{},{},{},{},{},{},{}", csv_field(name), r.rank, r.hand, csv_field(&r.hand_type), r.decided_by, r.bid, r.winnings);
+                }
+                continue;
+            }
+            if table {
+                println!("{:>6} {:<8} {:<16} {:<20} {:>6} {:>10}", "Rank", "Hand", "Type", "Decided by", "Bid", "Winnings");
+                for r in set.ranking() {
+                    println!("{:>6} {:<8} {:<16} {:<20} {:>6} {:>10}", r.rank, r.hand, r.hand_type, r.decided_by, r.bid, r.winnings);
+                }
+            }
+            println!("{} winnings: {}", name, set.winnings());
+        }
     } else {
-        println!("Please provide 1 argument: Filename");
+        println!("Please provide 1 argument: Filename [--rules=standard|jokers|WILDCARDS] [--type=NAME:COUNTS] [--report|--report=csv]");
     }
 }
@@ -1,4 +1,5 @@
 use std::fs;
+use std::fmt;
 use std::env;
 use std::str::FromStr;
 use std::collections::HashMap;
@@ -21,79 +22,502 @@ impl Direction {
 
 #[derive(Debug)]
 struct Node {
-    left: String,
-    right: String
+    left: usize,
+    right: usize
 }
 
 #[derive(Debug)]
-struct Map(HashMap<String, Node>);
+struct Map {
+    names: Vec<String>,
+    ids: HashMap<String, usize>,
+    nodes: Vec<Node>
+}
 
 impl FromStr for Map {
     type Err = String;
 
     fn from_str(text: &str) -> Result<Self, Self::Err> {
-        let mut map = HashMap::new();
+        let mut definitions = Vec::new();
         for line in text.lines() {
+            if line.trim().is_empty() {
+                continue;
+            }
             //AAA = (BBB, CCC)
-            map.insert(line[0..3].to_string(), Node { left: line[7..10].to_string(), right: line[12..15].to_string()});
+            let (name, children) = line.split_once('=').ok_or(format!("Expected '=' in line: {}", line))?;
+            let children = children.trim();
+            if !children.starts_with('(') || !children.ends_with(')') {
+                return Err(format!("Expected (LEFT, RIGHT) in line: {}", line));
+            }
+            let (left, right) = children[1..children.len() - 1].split_once(',').ok_or(format!("Expected ',' in line: {}", line))?;
+            definitions.push((parse_name(name)?, parse_name(left)?, parse_name(right)?));
         }
-        Ok(Self(map))
+        let mut names = Vec::new();
+        let mut ids = HashMap::new();
+        for (name, _, _) in &definitions {
+            if ids.insert(name.to_string(), names.len()).is_some() {
+                return Err(format!("Node {} is defined more than once", name));
+            }
+            names.push(name.to_string());
+        }
+        let mut nodes = Vec::new();
+        for (name, left, right) in &definitions {
+            let left = *ids.get(*left).ok_or(format!("Node {} references undefined node {}", name, left))?;
+            let right = *ids.get(*right).ok_or(format!("Node {} references undefined node {}", name, right))?;
+            nodes.push(Node { left, right });
+        }
+        Ok(Self {
+            names,
+            ids,
+            nodes
+        })
+    }
+}
+
+fn parse_name(text: &str) -> Result<&str, String> {
+    let name = text.trim();
+    if name.is_empty() {
+        Err("Node name cannot be empty".to_string())
+    } else if name.contains(|c: char| c.is_whitespace() || "=(),".contains(c)) {
+        Err(format!("Invalid node name: {}", name))
+    } else {
+        Ok(name)
+    }
+}
+
+#[derive(Debug)]
+enum Selector {
+    Glob(String),
+    List(Vec<String>)
+}
+
+impl FromStr for Selector {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        // *A or {ZZZ,QQZ} or ZZZ,QQZ
+        let text = text.trim();
+        if text.starts_with('{') || text.contains(',') {
+            let inner = text.strip_prefix('{').unwrap_or(text);
+            let inner = inner.strip_suffix('}').unwrap_or(inner);
+            let names: Vec<String> = inner.split(',').map(|n| parse_name(n).map(|n| n.to_string())).collect::<Result<_, _>>()?;
+            Ok(Self::List(names))
+        } else if text.is_empty() {
+            Err("Selector cannot be empty".to_string())
+        } else {
+            Ok(Self::Glob(text.to_string()))
+        }
+    }
+}
+
+impl Selector {
+    fn matches(&self, name: &str) -> bool {
+        match self {
+            Self::Glob(pattern) => glob_matches(&pattern.chars().collect::<Vec<_>>(), &name.chars().collect::<Vec<_>>()),
+            Self::List(names) => names.iter().any(|n| n == name)
+        }
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Glob(pattern) => write!(f, "{}", pattern),
+            Self::List(names) => write!(f, "{{{}}}", names.join(","))
+        }
+    }
+}
+
+fn glob_matches(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|i| glob_matches(&pattern[1..], &name[i..])),
+        Some('?') => !name.is_empty() && glob_matches(&pattern[1..], &name[1..]),
+        Some(c) => name.first() == Some(c) && glob_matches(&pattern[1..], &name[1..])
     }
 }
 
 impl Map {
-    fn steps(&self, from: &str, to: &str, directions: &Vec<Direction>) -> usize {
+    fn next(&self, id: usize, direction: &Direction) -> usize {
+        let node = &self.nodes[id];
+        match direction {
+            Direction::Left => node.left,
+            Direction::Right => node.right
+        }
+    }
+
+    fn select(&self, selector: &Selector) -> Result<Vec<usize>, String> {
+        let ids = match selector {
+            Selector::Glob(_) => (0..self.nodes.len()).filter(|id| selector.matches(&self.names[*id])).collect(),
+            Selector::List(names) => names.iter().map(|n| self.ids.get(n).copied().ok_or(format!("Node {} not found", n))).collect::<Result<Vec<_>, _>>()?
+        };
+        if ids.is_empty() {
+            Err(format!("No nodes match {}", selector))
+        } else {
+            Ok(ids)
+        }
+    }
+
+    fn mask(&self, selector: &Selector) -> Result<Vec<bool>, String> {
+        let mut mask = vec![false; self.nodes.len()];
+        for id in self.select(selector)? {
+            mask[id] = true;
+        }
+        Ok(mask)
+    }
+
+    fn steps(&self, from: usize, to: &Selector, directions: &[Direction]) -> Result<usize, String> {
+        let end = self.mask(to)?;
+        let mut visited = vec![false; self.nodes.len() * directions.len()];
         let mut i = 0;
         let mut current = from;
-        while current != to {
-            let node = self.0.get(current).unwrap();
-            current = match directions[i % directions.len()] {
-                Direction::Left => &node.left,
-                Direction::Right => &node.right
-            };
+        while !end[current] {
+            let state = current * directions.len() + i % directions.len();
+            if visited[state] {
+                // back in the same place at the same point in the directions, so it will never finish
+                return Err(format!("{} never reaches {}", self.names[from], to));
+            }
+            visited[state] = true;
+            current = self.next(current, &directions[i % directions.len()]);
             i += 1;
         }
-        i
+        Ok(i)
     }
 
-    fn ghost_steps(&self, directions: &Vec<Direction>) -> usize {
+    fn ghost_steps(&self, from: &Selector, to: &Selector, directions: &[Direction]) -> Result<usize, String> {
+        let mut cycles: Vec<Cycle> = self.select(from)?.into_iter().map(|id| self.cycle(id, to, directions)).collect::<Result<_, _>>()?;
+        // before every ghost is in its cycle, just check each step
+        let offset = cycles.iter().map(|c| c.start).max().unwrap_or(0);
+        if let Some(i) = (0..offset).find(|i| cycles.iter().all(|c| c.at_end(*i))) {
+            return Ok(i);
+        }
+        // after that, each ghost is at an end on a fixed set of steps modulo its cycle length,
+        // so combine the ghosts with the fewest residues using the chinese remainder theorem, while that stays small
+        cycles.sort_by_key(|c| c.residues().len());
+        let mut modulus: u128 = 1;
+        let mut residues: Vec<u128> = vec![0];
+        let mut combined = 0;
+        for cycle in &cycles {
+            let cycle_residues = cycle.residues();
+            if residues.len() * cycle_residues.len() > MAX_RESIDUES {
+                break;
+            }
+            let length = cycle.length as u128;
+            let mut next = Vec::new();
+            for a in &residues {
+                for b in &cycle_residues {
+                    next.extend(crt(*a, modulus, *b, length));
+                }
+            }
+            next.sort();
+            next.dedup();
+            modulus = lcm(modulus, length).ok_or("Ghost cycles are too long to combine".to_string())?;
+            residues = next;
+            combined += 1;
+        }
+        // then walk the matching steps in increasing order, checking the remaining ghosts directly,
+        // until the whole pattern repeats or it is taking too long
+        let remaining = &cycles[combined..];
+        let period = remaining.iter().try_fold(modulus, |m, c| lcm(m, c.length as u128)).unwrap_or(u128::MAX);
+        let offset = offset as u128;
+        let base = offset - offset % modulus;
+        let mut checked = 0;
+        for k in 0.. {
+            for r in &residues {
+                let step = base + k * modulus + r;
+                if step < offset {
+                    continue;
+                }
+                if step - offset >= period {
+                    return Err(format!("The ghosts from {} never reach {} at the same time", from, to));
+                }
+                let step = usize::try_from(step).map_err(|_| format!("The ghosts from {} take too many steps to count", from))?;
+                if remaining.iter().all(|c| c.at_end(step)) {
+                    return Ok(step);
+                }
+                checked += 1;
+                if checked == MAX_CANDIDATES {
+                    return Err(format!("Gave up after checking {} steps where the ghosts from {} might all reach {}", checked, from, to));
+                }
+            }
+            if residues.is_empty() {
+                break;
+            }
+        }
+        Err(format!("The ghosts from {} never reach {} at the same time", from, to))
+    }
+
+    fn to_dot(&self, from: &Selector, to: &Selector) -> Result<String, String> {
+        let start = self.mask(from)?;
+        let end = self.mask(to)?;
+        let mut dot = String::from("digraph map {\n");
+        for (id, node) in self.nodes.iter().enumerate() {
+            let shape = match (start[id], end[id]) {
+                (true, true) => "doubleoctagon",
+                (true, false) => "box",
+                (false, true) => "doublecircle",
+                (false, false) => "ellipse"
+            };
+            let name = dot_quote(&self.names[id]);
+            dot.push_str(&format!("    {} [shape={}];\n", name, shape));
+            if node.left == node.right {
+                dot.push_str(&format!("    {} -> {} [label=\"LR\"];\n", name, dot_quote(&self.names[node.left])));
+            } else {
+                dot.push_str(&format!("    {} -> {} [label=\"L\"];\n", name, dot_quote(&self.names[node.left])));
+                dot.push_str(&format!("    {} -> {} [label=\"R\"];\n", name, dot_quote(&self.names[node.right])));
+            }
+        }
+        dot.push_str("}\n");
+        Ok(dot)
+    }
+
+    fn reachable(&self, from: &[usize]) -> Vec<bool> {
+        let mut reached = vec![false; self.nodes.len()];
+        let mut pending = from.to_vec();
+        while let Some(id) = pending.pop() {
+            if !reached[id] {
+                reached[id] = true;
+                pending.push(self.nodes[id].left);
+                pending.push(self.nodes[id].right);
+            }
+        }
+        reached
+    }
+
+    fn unreachable(&self, from: &Selector) -> Result<Vec<usize>, String> {
+        let reached = self.reachable(&self.select(from)?);
+        Ok((0..self.nodes.len()).filter(|id| !reached[*id]).collect())
+    }
+
+    fn reachable_ends(&self, from: usize, to: &Selector, directions: &[Direction]) -> Result<Vec<usize>, String> {
+        // following the directions, rather than every path through the graph
+        let mut ends: Vec<usize> = self.cycle(from, to, directions)?.ends.into_iter().map(|(_, id)| id).collect();
+        ends.sort();
+        ends.dedup();
+        Ok(ends)
+    }
+
+    fn strongly_connected_components(&self) -> Vec<Vec<usize>> {
+        // Kosaraju: order nodes by DFS finish time, then collect components on the reversed graph
+        let mut order = Vec::new();
+        let mut visited = vec![false; self.nodes.len()];
+        for root in 0..self.nodes.len() {
+            if visited[root] {
+                continue;
+            }
+            visited[root] = true;
+            let mut stack = vec![(root, 0)];
+            while let Some((id, child)) = stack.pop() {
+                let children = [self.nodes[id].left, self.nodes[id].right];
+                if child < children.len() {
+                    stack.push((id, child + 1));
+                    if !visited[children[child]] {
+                        visited[children[child]] = true;
+                        stack.push((children[child], 0));
+                    }
+                } else {
+                    order.push(id);
+                }
+            }
+        }
+        let mut parents = vec![Vec::new(); self.nodes.len()];
+        for (id, node) in self.nodes.iter().enumerate() {
+            parents[node.left].push(id);
+            if node.right != node.left {
+                parents[node.right].push(id);
+            }
+        }
+        let mut assigned = vec![false; self.nodes.len()];
+        let mut components = Vec::new();
+        for root in order.into_iter().rev() {
+            if assigned[root] {
+                continue;
+            }
+            assigned[root] = true;
+            let mut component = Vec::new();
+            let mut pending = vec![root];
+            while let Some(id) = pending.pop() {
+                component.push(id);
+                for parent in &parents[id] {
+                    if !assigned[*parent] {
+                        assigned[*parent] = true;
+                        pending.push(*parent);
+                    }
+                }
+            }
+            component.sort();
+            components.push(component);
+        }
+        components
+    }
+
+    fn cycle(&self, from: usize, to: &Selector, directions: &[Direction]) -> Result<Cycle, String> {
+        let end = self.mask(to)?;
+        let mut first_seen = vec![None; self.nodes.len() * directions.len()];
+        let mut ends = Vec::new();
         let mut i = 0;
-        let mut current = Vec::new();
-        for k in self.0.keys() {
-            if k.chars().nth(2).unwrap() == 'A' {
-                current.push(k);
-            }
-        }
-        while current.iter().any(|k| k.chars().nth(2).unwrap() != 'Z') {
-            let mut new = Vec::new();
-            for c in current {
-                let node = self.0.get(c).unwrap();
-                new.push(match directions[i % directions.len()] {
-                    Direction::Left => &node.left,
-                    Direction::Right => &node.right
+        let mut current = from;
+        loop {
+            let state = current * directions.len() + i % directions.len();
+            if let Some(start) = first_seen[state] {
+                let mut hits = vec![false; i];
+                for (step, _) in &ends {
+                    hits[*step] = true;
+                }
+                return Ok(Cycle {
+                    start,
+                    length: i - start,
+                    ends,
+                    hits
                 });
             }
-            current = new;
+            first_seen[state] = Some(i);
+            if end[current] {
+                ends.push((i, current));
+            }
+            current = self.next(current, &directions[i % directions.len()]);
             i += 1;
         }
-        i
+    }
+}
+
+fn dot_quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+const MAX_RESIDUES: usize = 100_000;
+const MAX_CANDIDATES: usize = 10_000_000;
+
+#[derive(Debug)]
+struct Cycle {
+    start: usize,
+    length: usize,
+    ends: Vec<(usize, usize)>,
+    // whether each step before the cycle repeats is at an end
+    hits: Vec<bool>
+}
+
+impl Cycle {
+    fn at_end(&self, step: usize) -> bool {
+        let step = if step < self.start { step } else { self.start + (step - self.start) % self.length };
+        self.hits[step]
+    }
+
+    fn residues(&self) -> Vec<u128> {
+        self.ends.iter().filter(|(step, _)| *step >= self.start).map(|(step, _)| (*step % self.length) as u128).collect()
+    }
+}
+
+fn gcd(a: u128, b: u128) -> u128 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+fn lcm(a: u128, b: u128) -> Option<u128> {
+    (a / gcd(a, b)).checked_mul(b)
+}
+
+fn crt(a: u128, m: u128, b: u128, n: u128) -> Option<u128> {
+    // the x modulo lcm(m, n) where x = a (mod m) and x = b (mod n), if there is one
+    let g = gcd(m, n);
+    let difference = (b + n - a % n) % n;
+    if !difference.is_multiple_of(g) {
+        return None;
+    }
+    // step from a in multiples of m until also matching b, which takes (difference / g) * inverse(m / g) steps modulo n / g
+    let reduced = n / g;
+    let steps = mul_mod(difference / g, inverse(m / g % reduced, reduced)?, reduced);
+    let l = lcm(m, n)?;
+    Some((a + mul_mod(steps, m, l)) % l)
+}
+
+fn mul_mod(a: u128, b: u128, m: u128) -> u128 {
+    // double and add, since a * b may overflow even when m does not
+    let mut result = 0;
+    let mut a = a % m;
+    let mut b = b;
+    while b > 0 {
+        if b % 2 == 1 {
+            result = (result + a) % m;
+        }
+        a = (a + a) % m;
+        b /= 2;
+    }
+    result
+}
+
+fn inverse(a: u128, m: u128) -> Option<u128> {
+    // extended euclid, tracking coefficients as signed values
+    if m == 1 {
+        return Some(0);
+    }
+    let (mut old_r, mut r) = (a as i128, m as i128);
+    let (mut old_s, mut s) = (1i128, 0i128);
+    while r != 0 {
+        let q = old_r / r;
+        (old_r, r) = (r, old_r - q * r);
+        (old_s, s) = (s, old_s - q * s);
+    }
+    if old_r != 1 {
+        None
+    } else {
+        Some(old_s.rem_euclid(m as i128) as u128)
     }
 }
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    if args.len() == 2 {
+    let mode = args.iter().skip(1).find(|a| a.starts_with("--")).cloned();
+    let args: Vec<String> = args.into_iter().filter(|a| !a.starts_with("--")).collect();
+    if args.len() == 2 || args.len() == 4 {
         let filename = &args[1];
-        let text = fs::read_to_string(&filename)
-            .expect(&format!("Error reading from {}", filename));
-        let sections: Vec<&str> = text.split("\r\n\r\n").collect();
-        let directions: Vec<Direction> = sections[0].chars().map(|c| Direction::from(c)).collect();
-        let map: Map = sections[1].parse().unwrap();
+        let text = fs::read_to_string(filename)
+            .unwrap_or_else(|_| panic!("Error reading from {}", filename));
+        let (directions, map) = text.split_once("\n\n").or(text.split_once("\r\n\r\n"))
+            .expect("Expected directions and map separated by a blank line");
+        let directions: Vec<Direction> = directions.trim().chars().map(Direction::from).collect();
+        let map: Map = map.parse().unwrap();
         // println!("Directions: {:?}", directions);
         // println!("Map: {:?}", map);
-        //println!("AAA-ZZZ in {} steps", map.steps("AAA", "ZZZ", &directions));
-        println!("AAA-ZZZ in {} ghost steps", map.ghost_steps(&directions));
+        let (from, to): (Selector, Selector) = if args.len() == 4 {
+            (args[2].parse().unwrap(), args[3].parse().unwrap())
+        } else {
+            ("*A".parse().unwrap(), "*Z".parse().unwrap())
+        };
+        match mode.as_deref() {
+            None => {
+                for id in map.select(&from).unwrap() {
+                    match map.steps(id, &to, &directions) {
+                        Ok(steps) => println!("{} in {} steps", map.names[id], steps),
+                        Err(message) => println!("{}", message)
+                    }
+                }
+                match map.ghost_steps(&from, &to, &directions) {
+                    Ok(steps) => println!("All in {} ghost steps", steps),
+                    Err(message) => println!("{}", message)
+                }
+            },
+            Some("--dot") => print!("{}", map.to_dot(&from, &to).unwrap()),
+            Some("--analyse") => {
+                let names = |ids: &[usize]| ids.iter().map(|id| map.names[*id].as_str()).collect::<Vec<_>>().join(", ");
+                println!("Unreachable from {}: [{}]", from, names(&map.unreachable(&from).unwrap()));
+                let components: Vec<Vec<usize>> = map.strongly_connected_components().into_iter().filter(|c| c.len() > 1).collect();
+                println!("{} strongly connected components with more than 1 node:", components.len());
+                for component in components {
+                    println!("  [{}]", names(&component));
+                }
+                for id in map.select(&from).unwrap() {
+                    let cycle = map.cycle(id, &to, &directions).unwrap();
+                    println!("{} reaches [{}] following the directions", map.names[id], names(&map.reachable_ends(id, &to, &directions).unwrap()));
+                    println!("  enters a cycle of {} steps after {} steps", cycle.length, cycle.start);
+                    for (step, end) in cycle.ends {
+                        let repeats = if step >= cycle.start { " (repeating)" } else { "" };
+                        println!("  reaches {} at step {}{}", map.names[end], step, repeats);
+                    }
+                }
+            },
+            Some(other) => println!("Unknown mode: {}", other)
+        }
     } else {
-        println!("Please provide 1 argument: Filename");
+        println!("Please provide 1 or 3 arguments: Filename [Start End] [--dot|--analyse]");
+        println!("Start and End can be a glob (eg. '*A') or a list of nodes (eg. '{{ZZZ,QQZ}}'), quoted so the shell leaves them alone");
     }
 }